@@ -0,0 +1,133 @@
+//! Command-line configuration, shared by the `rust-tcp-latency` client and
+//! server binaries.
+//!
+//! Parsing is a hand-rolled pass over `std::env::args()` rather than a
+//! `clap`/`structopt` derive, matching the rest of this crate's preference
+//! for small, dependency-free building blocks.
+
+use std::env;
+
+/// Which benchmark transport `parse_config` selected via `--proto`.
+/// `Stream` is the default connection-oriented AF_VSOCK/TCP path; `Datagram`
+/// exercises the connectionless SOCK_DGRAM path; `PacketMmap` drives the
+/// zero-copy `AF_PACKET`/`PACKET_MMAP` ring-buffer mode instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    Stream,
+    Datagram,
+    PacketMmap,
+}
+
+impl Protocol {
+    fn parse(value: &str) -> Protocol {
+        match value {
+            "stream" | "tcp" | "vsock-stream" => Protocol::Stream,
+            "dgram" | "udp" | "vsock-dgram" => Protocol::Datagram,
+            "packet-mmap" => Protocol::PacketMmap,
+            other => panic!("Unknown --proto value: {} (expected stream, dgram or packet-mmap)", other),
+        }
+    }
+}
+
+pub struct Args {
+    pub address: String,
+    pub n_bytes: usize,
+    pub n_rounds: usize,
+    pub proto: Protocol,
+    /// Target send rate in bytes/sec, enforced by `throttle`. `None` means
+    /// send as fast as possible.
+    pub rate: Option<u64>,
+    /// SO_SNDTIMEO/SO_RCVTIMEO applied to the benchmark socket. `None` means
+    /// the socket stays blocking, same as before this option existed.
+    pub timeout_ms: Option<u64>,
+    /// Network interface index `Protocol::PacketMmap` binds its AF_PACKET
+    /// socket to.
+    pub interface_index: i32,
+    pub ring_block_size: u32,
+    pub ring_block_count: u32,
+    pub ring_frame_size: u32,
+    pub ring_frame_count: u32,
+}
+
+impl Default for Args {
+    fn default() -> Self {
+        Args {
+            address: "127.0.0.1:5001".to_string(),
+            n_bytes: 1024,
+            n_rounds: 1000,
+            proto: Protocol::Stream,
+            rate: None,
+            timeout_ms: None,
+            interface_index: 0,
+            ring_block_size: 1 << 12,
+            ring_block_count: 64,
+            ring_frame_size: 1 << 11,
+            ring_frame_count: 128,
+        }
+    }
+}
+
+/// Parse the process's command-line arguments into `Args`, falling back to
+/// `Args::default()` for anything not passed.
+pub fn parse_config() -> Args {
+    let mut args = Args::default();
+    let mut it = env::args().skip(1);
+
+    while let Some(flag) = it.next() {
+        match flag.as_str() {
+            "--address" => args.address = it.next().expect("--address requires a value"),
+            "--n-bytes" => {
+                args.n_bytes = it.next().expect("--n-bytes requires a value").parse().expect("--n-bytes must be a number")
+            }
+            "--n-rounds" => {
+                args.n_rounds = it.next().expect("--n-rounds requires a value").parse().expect("--n-rounds must be a number")
+            }
+            "--proto" => args.proto = Protocol::parse(&it.next().expect("--proto requires a value")),
+            "--rate" => {
+                args.rate = Some(it.next().expect("--rate requires a value").parse().expect("--rate must be a number of bytes/sec"))
+            }
+            "--timeout-ms" => {
+                args.timeout_ms =
+                    Some(it.next().expect("--timeout-ms requires a value").parse().expect("--timeout-ms must be a number"))
+            }
+            "--interface-index" => {
+                args.interface_index = it
+                    .next()
+                    .expect("--interface-index requires a value")
+                    .parse()
+                    .expect("--interface-index must be a number")
+            }
+            "--ring-block-size" => {
+                args.ring_block_size = it
+                    .next()
+                    .expect("--ring-block-size requires a value")
+                    .parse()
+                    .expect("--ring-block-size must be a number")
+            }
+            "--ring-block-count" => {
+                args.ring_block_count = it
+                    .next()
+                    .expect("--ring-block-count requires a value")
+                    .parse()
+                    .expect("--ring-block-count must be a number")
+            }
+            "--ring-frame-size" => {
+                args.ring_frame_size = it
+                    .next()
+                    .expect("--ring-frame-size requires a value")
+                    .parse()
+                    .expect("--ring-frame-size must be a number")
+            }
+            "--ring-frame-count" => {
+                args.ring_frame_count = it
+                    .next()
+                    .expect("--ring-frame-count requires a value")
+                    .parse()
+                    .expect("--ring-frame-count must be a number")
+            }
+            other => panic!("Unknown flag: {}", other),
+        }
+    }
+
+    args
+}
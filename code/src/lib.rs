@@ -0,0 +1,9 @@
+//! Shared support code for the `rust-tcp-latency` client/server binaries:
+//! CLI configuration (`config`) and the latency histogram summary printer
+//! (`print_utils`), plus a re-export of `nix` so the binaries can reach the
+//! exact version this crate was built against.
+
+pub extern crate nix;
+
+pub mod config;
+pub mod print_utils;
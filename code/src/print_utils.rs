@@ -0,0 +1,10 @@
+//! Formats an `hdrhist::HDRHist` round-trip latency histogram for the
+//! terminal once a benchmark run completes.
+
+/// Print the summary (the usual percentile ladder, in the histogram's own
+/// unit: nanoseconds, as the client records them) of a round-trip latency
+/// histogram.
+pub fn print_summary(hist: hdrhist::HDRHist) {
+    println!("Summary:");
+    print!("{}", hist.summary_string());
+}
@@ -1,32 +1,116 @@
 extern crate bytes;
 extern crate rust_tcp_io_perf;
 extern crate hdrhist;
+#[cfg(target_os = "linux")]
+extern crate libc;
 
-use std::time::Instant;
+#[cfg(unix)]
+mod reconnect;
+#[cfg(windows)]
+mod windows;
+#[cfg(target_os = "linux")]
+mod packet_mmap;
+mod transport;
+
+use std::time::{Duration, Instant};
 use std::{thread, time};
 use rust_tcp_io_perf::config;
+use rust_tcp_io_perf::config::Protocol;
 use std::convert::TryInto;
 
+use std::fmt;
+#[cfg(unix)]
 use std::os::unix::io::{AsRawFd, RawFd};
-use rust_tcp_io_perf::nix::sys::socket::{connect, shutdown, socket, send, recv};
+#[cfg(unix)]
+use rust_tcp_io_perf::nix::sys::socket::{connect, setsockopt, shutdown, socket, send, recv};
+#[cfg(unix)]
 use rust_tcp_io_perf::nix::sys::socket::{AddressFamily, Shutdown, SockAddr, SockFlag, SockType};
+#[cfg(unix)]
+use rust_tcp_io_perf::nix::sys::socket::sockopt::{RcvTimeo, SndTimeo};
+#[cfg(unix)]
+use rust_tcp_io_perf::nix::sys::time::TimeVal;
+#[cfg(unix)]
 use rust_tcp_io_perf::nix::unistd::close;
-use rust_tcp_io_perf::nix::errno::Errno::EINTR;
+#[cfg(unix)]
+use rust_tcp_io_perf::nix::errno::Errno::{EAGAIN, EINTR, EWOULDBLOCK};
+#[cfg(unix)]
 use rust_tcp_io_perf::nix::sys::socket::MsgFlags;
 use rust_tcp_io_perf::print_utils;
+use transport::BenchTransport;
+#[cfg(unix)]
+use reconnect::ReconnectSocket;
+#[cfg(windows)]
+use windows::TcpTransport;
+
+#[cfg(unix)]
+type Transport = ReconnectSocket;
+#[cfg(windows)]
+type Transport = TcpTransport;
 
+#[cfg(unix)]
 const MAX_CONNECTION_ATTEMPTS: usize = 5;
+// Size, in bytes, of the sequence number tag written into the front of every
+// datagram so lost/reordered packets can be detected on the receive side.
+#[cfg(unix)]
+const SEQ_HEADER_LEN: usize = 8;
+// Fallback SO_RCVTIMEO for datagram mode when `--timeout-ms` isn't set.
+// Datagram loss detection in `dgram_round_trip` only works if `recv` can time
+// out, so this mode needs a recv timeout regardless of whether the user
+// asked for one; a lost packet otherwise blocks `recv` forever since no echo
+// is ever coming.
+#[cfg(unix)]
+const DGRAM_DEFAULT_TIMEOUT_MS: u64 = 1000;
+
+/// Error from a send/recv loop. Kept distinct from a plain `String` so
+/// callers can tell a send/receive timeout (recoverable: drop the round and
+/// keep going) apart from every other failure (fatal, same as before).
+#[derive(Debug)]
+pub enum LoopError {
+    Timeout,
+    Other(String),
+}
+
+impl fmt::Display for LoopError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoopError::Timeout => write!(f, "send/recv timed out"),
+            LoopError::Other(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn is_timeout(err: &nix::Error) -> bool {
+    matches!(err, nix::Error::Sys(EAGAIN) | nix::Error::Sys(EWOULDBLOCK))
+}
 
+/// Apply `SO_SNDTIMEO`/`SO_RCVTIMEO` to `fd` so a stalled peer causes
+/// `send`/`recv` to fail with `EAGAIN`/`EWOULDBLOCK` instead of blocking
+/// forever. A `None` timeout leaves the socket blocking, matching the
+/// previous behaviour.
+#[cfg(unix)]
+fn set_timeouts(fd: RawFd, timeout_ms: Option<u64>) -> Result<(), String> {
+    if let Some(timeout_ms) = timeout_ms {
+        let timeout = TimeVal::milliseconds(timeout_ms as i64);
+        setsockopt(fd, SndTimeo, &timeout).map_err(|err| format!("Failed to set send timeout: {:?}", err))?;
+        setsockopt(fd, RcvTimeo, &timeout).map_err(|err| format!("Failed to set receive timeout: {:?}", err))?;
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
 struct VsockSocket {
     socket_fd: RawFd,
 }
 
+#[cfg(unix)]
 impl VsockSocket {
     fn new(socket_fd: RawFd) -> Self {
         VsockSocket { socket_fd }
     }
 }
 
+#[cfg(unix)]
 impl Drop for VsockSocket {
     fn drop(&mut self) {
         shutdown(self.socket_fd, Shutdown::Both)
@@ -35,21 +119,24 @@ impl Drop for VsockSocket {
     }
 }
 
+#[cfg(unix)]
 impl AsRawFd for VsockSocket {
     fn as_raw_fd(&self) -> RawFd {
         self.socket_fd
     }
 }
 
-pub fn send_loop(fd: RawFd, buf: &[u8], len: u64) -> Result<(), String> {
-    let len: usize = len.try_into().map_err(|err| format!("{:?}", err))?;
+#[cfg(unix)]
+pub fn send_loop(fd: RawFd, buf: &[u8], len: u64) -> Result<(), LoopError> {
+    let len: usize = len.try_into().map_err(|err| LoopError::Other(format!("{:?}", err)))?;
     let mut send_bytes = 0;
 
     while send_bytes < len {
         let size = match send(fd, &buf[send_bytes..len], MsgFlags::empty()) {
             Ok(size) => size,
             Err(nix::Error::Sys(EINTR)) => 0,
-            Err(err) => return Err(format!("{:?}", err)),
+            Err(err) if is_timeout(&err) => return Err(LoopError::Timeout),
+            Err(err) => return Err(LoopError::Other(format!("{:?}", err))),
         };
         send_bytes += size;
     }
@@ -58,15 +145,17 @@ pub fn send_loop(fd: RawFd, buf: &[u8], len: u64) -> Result<(), String> {
 }
 
 /// Receive `len` bytes from a connection-orriented socket
-pub fn recv_loop(fd: RawFd, buf: &mut [u8], len: u64) -> Result<(), String> {
-    let len: usize = len.try_into().map_err(|err| format!("{:?}", err))?;
+#[cfg(unix)]
+pub fn recv_loop(fd: RawFd, buf: &mut [u8], len: u64) -> Result<(), LoopError> {
+    let len: usize = len.try_into().map_err(|err| LoopError::Other(format!("{:?}", err)))?;
     let mut recv_bytes = 0;
 
     while recv_bytes < len {
         let size = match recv(fd, &mut buf[recv_bytes..len], MsgFlags::empty()) {
             Ok(size) => size,
             Err(nix::Error::Sys(EINTR)) => 0,
-            Err(err) => return Err(format!("{:?}", err)),
+            Err(err) if is_timeout(&err) => return Err(LoopError::Timeout),
+            Err(err) => return Err(LoopError::Other(format!("{:?}", err))),
         };
         recv_bytes += size;
     }
@@ -74,8 +163,12 @@ pub fn recv_loop(fd: RawFd, buf: &mut [u8], len: u64) -> Result<(), String> {
     Ok(())
 }
 
-/// Initiate a connection on an AF_VSOCK socket
-fn vsock_connect(cid: u32, port: u32) -> Result<VsockSocket, String> {
+/// Initiate a connection on an AF_VSOCK socket, using `sock_type` to choose
+/// between the connection-oriented (`SockType::Stream`) and datagram
+/// (`SockType::Dgram`) transports. `timeout_ms`, when set, is applied to the
+/// connected socket as `SO_SNDTIMEO`/`SO_RCVTIMEO`.
+#[cfg(unix)]
+fn vsock_connect(cid: u32, port: u32, sock_type: SockType, timeout_ms: Option<u64>) -> Result<VsockSocket, String> {
     let sockaddr = SockAddr::new_vsock(cid, port);
     let mut err_msg = String::new();
 
@@ -83,14 +176,17 @@ fn vsock_connect(cid: u32, port: u32) -> Result<VsockSocket, String> {
         let vsocket = VsockSocket::new(
             socket(
                 AddressFamily::Vsock,
-                SockType::Stream,
+                sock_type,
                 SockFlag::empty(),
                 None,
             )
             .map_err(|err| format!("Failed to create the socket: {:?}", err))?,
         );
         match connect(vsocket.as_raw_fd(), &sockaddr) {
-            Ok(_) => return Ok(vsocket),
+            Ok(_) => {
+                set_timeouts(vsocket.as_raw_fd(), timeout_ms)?;
+                return Ok(vsocket);
+            }
             Err(e) => err_msg = format!("Failed to connect: {}", e),
         }
 
@@ -101,50 +197,193 @@ fn vsock_connect(cid: u32, port: u32) -> Result<VsockSocket, String> {
     Err(err_msg)
 }
 
-fn main() {
+/// Write a monotonically increasing sequence number into the first
+/// `SEQ_HEADER_LEN` bytes of `buf` so the receiver can tell lost or
+/// reordered datagrams apart from the one it just sent.
+#[cfg(unix)]
+fn tag_sequence(buf: &mut [u8], seq: u64) {
+    buf[..SEQ_HEADER_LEN].copy_from_slice(&seq.to_be_bytes());
+}
 
-    let args = config::parse_config();
+#[cfg(unix)]
+fn read_sequence(buf: &[u8]) -> u64 {
+    u64::from_be_bytes(buf[..SEQ_HEADER_LEN].try_into().unwrap())
+}
 
-    println!("Connecting to the server {}...", args.address);
-    let n_rounds = args.n_rounds;
-    let n_bytes = args.n_bytes;
+/// Sleep the remainder of the ideal inter-send interval so the offered load
+/// stays under `rate` bytes/sec, letting users characterize latency under a
+/// capped load rather than only the fully saturated case. A no-op when
+/// `rate` is `None` or `Some(0)` - a rate of zero has no achievable ideal
+/// interval, so it is treated as "don't throttle" rather than dividing by
+/// zero and panicking in `Duration::from_secs_f64`.
+fn throttle(rate: Option<u64>, n_bytes: usize, elapsed: Duration) {
+    if let Some(rate) = rate {
+        if rate == 0 {
+            return;
+        }
+        let ideal = Duration::from_secs_f64(n_bytes as f64 / rate as f64);
+        if let Some(remainder) = ideal.checked_sub(elapsed) {
+            thread::sleep(remainder);
+        }
+    }
+}
+
+/// Send one tagged datagram and wait for its echo.
+///
+/// Unlike `send_loop`/`recv_loop`, a single `send`/`recv` pair is issued
+/// because datagrams are atomic: there is no "partial" UDP message to loop
+/// over. Returns `Ok(true)` when the echoed sequence number matches what was
+/// sent, and `Ok(false)` when a packet was lost or a stale/reordered one
+/// came back instead - the caller skips such round trips from the latency
+/// histogram rather than blocking for the "right" one.
+#[cfg(unix)]
+fn dgram_round_trip(fd: RawFd, wbuf: &mut [u8], rbuf: &mut [u8], seq: u64) -> Result<bool, LoopError> {
+    tag_sequence(wbuf, seq);
+    match send(fd, wbuf, MsgFlags::empty()) {
+        Ok(_) => {}
+        Err(err) if is_timeout(&err) => return Err(LoopError::Timeout),
+        Err(err) => return Err(LoopError::Other(format!("{:?}", err))),
+    }
+
+    match recv(fd, rbuf, MsgFlags::empty()) {
+        Ok(size) if size >= SEQ_HEADER_LEN => Ok(read_sequence(rbuf) == seq),
+        Ok(_) => Ok(false),
+        Err(err) if is_timeout(&err) => Ok(false),
+        Err(err) => Err(LoopError::Other(format!("{:?}", err))),
+    }
+}
 
-    // Create buffers to read/write
+/// Run the connection-oriented benchmark: a double pass of full `n_bytes`
+/// send/receive loops, measuring only the second half to let TCP slowstart
+/// settle before recording latency. Generic over `BenchTransport` so the
+/// same loop drives the Unix vsock transport and the Windows TCP transport;
+/// both survive a server restart mid-benchmark by redialing with backoff
+/// instead of aborting, so there is no outer `while !connected` loop here.
+fn run_stream_mode<T: BenchTransport>(args: &config::Args, n_rounds: usize, n_bytes: usize, rate: Option<u64>) {
     let wbuf: Vec<u8> = vec![0; n_bytes];
     let mut rbuf: Vec<u8> = vec![0; n_bytes];
 
     let progress_tracking_percentage = (n_rounds * 2) / 100;
 
+    let mut socket = T::connect(args);
+    let mut hist = hdrhist::HDRHist::new();
+    let mut bytes_transferred: u64 = 0;
+    let mut messages = 0u64;
+    let mut stalls = 0u64;
+    let mut measured_start = None;
+
+    println!("Connection established! Ready to send...");
+
+    // To avoid TCP slowstart we do double iterations and measure only the second half
+    for i in 0..(n_rounds * 2) {
+
+        let start = Instant::now();
+        let reconnects_before = socket.reconnects();
+
+        let round_trip = socket.send_loop(&wbuf, n_bytes.try_into().unwrap())
+            .and_then(|_| socket.recv_loop(&mut rbuf, n_bytes.try_into().unwrap()));
+
+        let duration = Instant::now().duration_since(start);
+        match round_trip {
+            Ok(()) => {
+                // A round that had to redial is counted in `reconnects()`
+                // only, not in the latency histogram.
+                if i >= n_rounds && socket.reconnects() == reconnects_before {
+                    measured_start.get_or_insert(start);
+                    hist.add_value(duration.as_secs() * 1_000_000_000u64 + duration.subsec_nanos() as u64);
+                    bytes_transferred += (n_bytes * 2) as u64;
+                    messages += 1;
+                }
+            }
+            Err(LoopError::Timeout) => stalls += 1,
+            Err(LoopError::Other(msg)) => panic!("Failed round trip: {}", msg),
+        }
+
+        if i % progress_tracking_percentage == 0 {
+            // Track progress on screen
+            println!("{}% completed", i / progress_tracking_percentage);
+        }
+
+        throttle(rate, n_bytes, duration);
+    }
+
+    println!("Reconnects: {}", socket.reconnects());
+    println!("Stalled (timed out) rounds: {}", stalls);
+    print_utils::print_summary(hist);
+    print_throughput(bytes_transferred, messages, measured_start.map_or(Duration::from_secs(0), |s| s.elapsed()));
+    socket.close();
+}
+
+/// Run the datagram benchmark: one `send`/`recv` per round instead of a
+/// byte-count loop, tagging every payload with a sequence number so lost or
+/// reordered packets can be excluded from the latency histogram and counted
+/// towards a packet-loss percentage instead. Unix/vsock only: datagrams
+/// don't fit the `BenchTransport` trait's connection-oriented shape, and
+/// there is no Windows implementation of this mode.
+#[cfg(unix)]
+fn run_dgram_mode(n_rounds: usize, n_bytes: usize, rate: Option<u64>, timeout_ms: Option<u64>) {
+    assert!(n_bytes >= SEQ_HEADER_LEN, "n_bytes must be at least {} to fit the sequence header", SEQ_HEADER_LEN);
+
+    let mut wbuf: Vec<u8> = vec![0; n_bytes];
+    let mut rbuf: Vec<u8> = vec![0; n_bytes];
+
+    let progress_tracking_percentage = (n_rounds * 2) / 100;
+
     let mut connected = false;
+    // `--timeout-ms` is an optional knob shared with the other modes, but
+    // datagram mode cannot work without a recv timeout at all: fall back to
+    // `DGRAM_DEFAULT_TIMEOUT_MS` so a genuinely lost packet is still counted
+    // as loss instead of hanging `recv` forever.
+    let recv_timeout_ms = Some(timeout_ms.unwrap_or(DGRAM_DEFAULT_TIMEOUT_MS));
 
     while !connected {
-        match vsock_connect(16, 5001) {
+        match vsock_connect(16, 5001, SockType::Dgram, recv_timeout_ms) {
             Ok(vsocket) => {
                 let fd = vsocket.as_raw_fd();
                 connected = true;
                 let mut hist = hdrhist::HDRHist::new();
+                let mut sent: u64 = 0;
+                let mut lost: u64 = 0;
+                let mut bytes_transferred: u64 = 0;
+                let mut messages: u64 = 0;
+                let mut measured_start = None;
 
                 println!("Connection established! Ready to send...");
 
-                // To avoid TCP slowstart we do double iterations and measure only the second half
+                // To avoid slowstart effects we do double iterations and measure only the second half
                 for i in 0..(n_rounds * 2) {
 
                     let start = Instant::now();
+                    sent += 1;
 
-                    send_loop(fd, &wbuf, n_bytes.try_into().unwrap()).expect("Failed send loop.");
-                    recv_loop(fd, &mut rbuf, n_bytes.try_into().unwrap()).expect("Failed receive loop.");
+                    let matched = match dgram_round_trip(fd, &mut wbuf, &mut rbuf, sent) {
+                        Ok(matched) => matched,
+                        Err(LoopError::Timeout) => false,
+                        Err(LoopError::Other(msg)) => panic!("Failed datagram round trip: {}", msg),
+                    };
 
                     let duration = Instant::now().duration_since(start);
-                    if i >= n_rounds {
+                    if !matched {
+                        lost += 1;
+                    } else if i >= n_rounds {
+                        measured_start.get_or_insert(start);
                         hist.add_value(duration.as_secs() * 1_000_000_000u64 + duration.subsec_nanos() as u64);
+                        bytes_transferred += (n_bytes * 2) as u64;
+                        messages += 1;
                     }
 
                     if i % progress_tracking_percentage == 0 {
                         // Track progress on screen
                         println!("{}% completed", i / progress_tracking_percentage);
                     }
+
+                    throttle(rate, n_bytes, duration);
                 }
+
+                let loss_percentage = (lost as f64 / sent as f64) * 100.0;
+                println!("Packet loss: {:.3}% ({}/{})", loss_percentage, lost, sent);
                 print_utils::print_summary(hist);
+                print_throughput(bytes_transferred, messages, measured_start.map_or(Duration::from_secs(0), |s| s.elapsed()));
             },
             Err(error) => {
                 println!("Couldn't connect to server, retrying... Error {}", error);
@@ -153,3 +392,82 @@ fn main() {
         }
     }
 }
+
+/// Run the zero-copy `PACKET_MMAP` benchmark: instead of one `send`/`recv`
+/// syscall per message, a whole batch of `geometry.frame_count` frames is
+/// filled directly in the mapped ring and flushed with a single `send`, so
+/// the histogram measures batch completion time rather than per-message
+/// latency. This is how the benchmark reaches line rate on a NIC, which
+/// `send_loop`/`recv_loop`'s one-syscall-per-message design cannot.
+#[cfg(target_os = "linux")]
+fn run_packet_mmap_mode(n_rounds: usize, n_bytes: usize, interface_index: i32, geometry: packet_mmap::RingGeometry) {
+    let mut ring = packet_mmap::PacketMmapRing::open(interface_index, geometry)
+        .expect("Failed to open the PACKET_MMAP ring");
+
+    let payload = vec![0u8; n_bytes];
+    let batch_size = geometry.frame_count as usize;
+    let batch: Vec<&[u8]> = vec![payload.as_slice(); batch_size];
+
+    let mut hist = hdrhist::HDRHist::new();
+
+    // To avoid slowstart effects we do double iterations and measure only the second half
+    for i in 0..(n_rounds * 2) {
+        let start = Instant::now();
+
+        ring.send_batch(&batch).expect("Failed to flush a PACKET_MMAP TX batch");
+        ring.recv_batch(batch_size);
+
+        let duration = Instant::now().duration_since(start);
+        if i >= n_rounds {
+            hist.add_value(duration.as_secs() * 1_000_000_000u64 + duration.subsec_nanos() as u64);
+        }
+    }
+
+    print_utils::print_summary(hist);
+}
+
+/// Print aggregate throughput (MB/s and messages/s) over the measured half
+/// of the benchmark, complementing the per-round-trip latency summary from
+/// `print_utils::print_summary` with the sustained-load view.
+fn print_throughput(bytes_transferred: u64, messages: u64, elapsed: Duration) {
+    let seconds = elapsed.as_secs_f64();
+    if seconds <= 0.0 {
+        return;
+    }
+    let mb_per_sec = (bytes_transferred as f64 / 1_000_000.0) / seconds;
+    let msgs_per_sec = messages as f64 / seconds;
+    println!("Throughput: {:.3} MB/s, {:.1} messages/s", mb_per_sec, msgs_per_sec);
+}
+
+fn dispatch(args: &config::Args) {
+    match args.proto {
+        Protocol::Stream => run_stream_mode::<Transport>(args, args.n_rounds, args.n_bytes, args.rate),
+        #[cfg(unix)]
+        Protocol::Datagram => run_dgram_mode(args.n_rounds, args.n_bytes, args.rate, args.timeout_ms),
+        #[cfg(not(unix))]
+        Protocol::Datagram => panic!("Datagram mode needs AF_VSOCK and is not available on this platform; this benchmark is TCP-only here."),
+        #[cfg(target_os = "linux")]
+        Protocol::PacketMmap => run_packet_mmap_mode(
+            args.n_rounds,
+            args.n_bytes,
+            args.interface_index,
+            packet_mmap::RingGeometry {
+                block_size: args.ring_block_size,
+                block_count: args.ring_block_count,
+                frame_size: args.ring_frame_size,
+                frame_count: args.ring_frame_count,
+            },
+        ),
+        #[cfg(not(target_os = "linux"))]
+        Protocol::PacketMmap => panic!("PACKET_MMAP mode needs AF_PACKET and is only available on Linux."),
+    }
+}
+
+fn main() {
+
+    let args = config::parse_config();
+
+    println!("Connecting to the server {}...", args.address);
+
+    dispatch(&args);
+}
@@ -0,0 +1,306 @@
+//! Zero-copy `AF_PACKET`/`PACKET_MMAP` transport for line-rate NIC
+//! benchmarking.
+//!
+//! `send_loop`/`recv_loop` pay one syscall per message, which caps
+//! throughput well below what a NIC can push. This mode instead maps the
+//! kernel's TX/RX ring (`PACKET_MMAP`) directly into userspace: frames are
+//! filled/read in place and a single `send`/poll flushes or drains a whole
+//! batch, so batch completion time - not per-message latency - is what goes
+//! into the histogram. Linux-only: `AF_PACKET` and `PACKET_MMAP` are Linux
+//! kernel features with no portable equivalent, which is why this lives
+//! behind `#[cfg(target_os = "linux")]` rather than joining `BenchTransport`.
+
+use std::io;
+use std::os::unix::io::RawFd;
+use std::ptr;
+
+use libc::{
+    c_void, mmap, sockaddr_ll, setsockopt, socket, AF_PACKET, ETH_P_ALL, MAP_SHARED, PROT_READ,
+    PROT_WRITE, SOCK_RAW, SOL_PACKET,
+};
+
+// Not exposed by the `libc` crate: PACKET_MMAP-specific constants from
+// <linux/if_packet.h>.
+const PACKET_TX_RING: libc::c_int = 13;
+const PACKET_RX_RING: libc::c_int = 5;
+const PACKET_VERSION: libc::c_int = 10;
+const TPACKET_V1: u32 = 0;
+
+const TP_STATUS_KERNEL: libc::c_ulong = 0;
+const TP_STATUS_USER: libc::c_ulong = 1;
+const TP_STATUS_SEND_REQUEST: libc::c_ulong = 1;
+
+// Every frame in a PACKET_MMAP ring is aligned to this boundary, and
+// `tp_mac` - the offset from the start of the frame to its payload - is
+// always a multiple of it too, per <linux/if_packet.h>.
+const TPACKET_ALIGNMENT: usize = 16;
+
+fn tpacket_align(len: usize) -> usize {
+    (len + TPACKET_ALIGNMENT - 1) & !(TPACKET_ALIGNMENT - 1)
+}
+
+// How long recv_batch's poll() waits for the next frame before giving up.
+// Chosen to comfortably exceed one RTT on a loopback/local NIC without
+// letting a dropped frame stall a whole batch indefinitely.
+const RECV_POLL_TIMEOUT_MS: i32 = 1000;
+
+/// Mirrors `struct tpacket_req` from <linux/if_packet.h>: the ring geometry
+/// passed to `setsockopt(SOL_PACKET, PACKET_{TX,RX}_RING)`.
+#[repr(C)]
+struct TpacketReq {
+    tp_block_size: u32,
+    tp_block_nr: u32,
+    tp_frame_size: u32,
+    tp_frame_nr: u32,
+}
+
+/// Mirrors `struct tpacket_hdr` (TPACKET_V1), which prefixes every frame in
+/// the mapped ring. `tp_status` is the kernel's `unsigned long` - 8 bytes on
+/// any 64-bit target, not 4 - so it's `libc::c_ulong` here rather than `u32`;
+/// getting this field's width wrong shifts every field after it off its real
+/// kernel offset.
+#[repr(C)]
+struct TpacketHdr {
+    tp_status: libc::c_ulong,
+    tp_len: u32,
+    tp_snaplen: u32,
+    tp_mac: u16,
+    tp_net: u16,
+    tp_sec: u32,
+    tp_usec: u32,
+}
+
+/// Ring geometry, surfaced through `config::parse_config` as `--ring-block-size`,
+/// `--ring-block-count`, `--ring-frame-size` and `--ring-frame-count`.
+#[derive(Clone, Copy)]
+pub struct RingGeometry {
+    pub block_size: u32,
+    pub block_count: u32,
+    pub frame_size: u32,
+    pub frame_count: u32,
+}
+
+impl RingGeometry {
+    fn req(&self) -> TpacketReq {
+        TpacketReq {
+            tp_block_size: self.block_size,
+            tp_block_nr: self.block_count,
+            tp_frame_size: self.frame_size,
+            tp_frame_nr: self.frame_count,
+        }
+    }
+
+    fn ring_bytes(&self) -> usize {
+        (self.block_size as usize) * (self.block_count as usize)
+    }
+
+    fn frames_per_ring(&self) -> u32 {
+        self.ring_bytes() as u32 / self.frame_size
+    }
+}
+
+/// A raw `AF_PACKET` socket with its TX and RX rings mapped into this
+/// process, so filling/draining frames touches shared memory instead of
+/// issuing a `send`/`recv` syscall per packet.
+pub struct PacketMmapRing {
+    fd: RawFd,
+    map: *mut c_void,
+    geometry: RingGeometry,
+    tx_cursor: u32,
+    rx_cursor: u32,
+}
+
+impl PacketMmapRing {
+    /// Open an `AF_PACKET`/`SOCK_RAW` socket on `interface_index`, install
+    /// `geometry` as both the TX and RX ring, and `mmap` the combined ring
+    /// (TX ring followed by RX ring, as the kernel lays them out).
+    pub fn open(interface_index: i32, geometry: RingGeometry) -> io::Result<Self> {
+        let fd = unsafe { socket(AF_PACKET, SOCK_RAW, (ETH_P_ALL as u16).to_be() as i32) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let version = TPACKET_V1;
+        let ret = unsafe {
+            setsockopt(
+                fd,
+                SOL_PACKET,
+                PACKET_VERSION,
+                &version as *const u32 as *const c_void,
+                std::mem::size_of::<u32>() as libc::socklen_t,
+            )
+        };
+        if ret < 0 {
+            let err = io::Error::last_os_error();
+            unsafe { libc::close(fd) };
+            return Err(err);
+        }
+
+        let req = geometry.req();
+        for &optname in &[PACKET_TX_RING, PACKET_RX_RING] {
+            let ret = unsafe {
+                setsockopt(
+                    fd,
+                    SOL_PACKET,
+                    optname,
+                    &req as *const TpacketReq as *const c_void,
+                    std::mem::size_of::<TpacketReq>() as libc::socklen_t,
+                )
+            };
+            if ret < 0 {
+                let err = io::Error::last_os_error();
+                unsafe { libc::close(fd) };
+                return Err(err);
+            }
+        }
+
+        let ring_len = geometry.ring_bytes();
+        let map = unsafe {
+            mmap(
+                ptr::null_mut(),
+                ring_len * 2, // TX ring followed by RX ring
+                PROT_READ | PROT_WRITE,
+                MAP_SHARED,
+                fd,
+                0,
+            )
+        };
+        if map == libc::MAP_FAILED {
+            let err = io::Error::last_os_error();
+            unsafe { libc::close(fd) };
+            return Err(err);
+        }
+
+        bind_to_interface(fd, interface_index)?;
+
+        Ok(PacketMmapRing {
+            fd,
+            map,
+            geometry,
+            tx_cursor: 0,
+            rx_cursor: 0,
+        })
+    }
+
+    fn tx_frame(&self, index: u32) -> *mut u8 {
+        unsafe { (self.map as *mut u8).add((index * self.geometry.frame_size) as usize) }
+    }
+
+    fn rx_frame(&self, index: u32) -> *mut u8 {
+        let rx_base = unsafe { (self.map as *mut u8).add(self.geometry.ring_bytes()) };
+        unsafe { rx_base.add((index * self.geometry.frame_size) as usize) }
+    }
+
+    /// Fill consecutive TX frames with `payloads`, mark each
+    /// `TP_STATUS_SEND_REQUEST`, then issue a single `send` to flush the
+    /// whole batch at once instead of one syscall per payload.
+    pub fn send_batch(&mut self, payloads: &[&[u8]]) -> io::Result<()> {
+        let frames_per_ring = self.geometry.frames_per_ring();
+        // `tp_mac` is the offset userspace is telling the kernel the payload
+        // starts at; it must be TPACKET_ALIGNMENT-aligned past the header,
+        // same as the kernel itself does on the RX side.
+        let mac_offset = tpacket_align(std::mem::size_of::<TpacketHdr>());
+
+        for payload in payloads {
+            let frame = self.tx_frame(self.tx_cursor);
+            let hdr = frame as *mut TpacketHdr;
+            unsafe {
+                ptr::copy_nonoverlapping(payload.as_ptr(), frame.add(mac_offset), payload.len());
+                (*hdr).tp_len = payload.len() as u32;
+                (*hdr).tp_mac = mac_offset as u16;
+                (*hdr).tp_status = TP_STATUS_SEND_REQUEST;
+            }
+            self.tx_cursor = (self.tx_cursor + 1) % frames_per_ring;
+        }
+
+        let ret = unsafe { libc::send(self.fd, ptr::null(), 0, 0) };
+        if ret < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Block in `poll(2)` on the ring's socket until it reports readable
+    /// data or `RECV_POLL_TIMEOUT_MS` elapses. `recv_batch` uses this to
+    /// actually wait for frames to turn `TP_STATUS_USER` instead of
+    /// sampling `tp_status` once and giving up.
+    fn poll_readable(&self) -> io::Result<bool> {
+        let mut pfd = libc::pollfd {
+            fd: self.fd,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let ret = unsafe { libc::poll(&mut pfd, 1, RECV_POLL_TIMEOUT_MS) };
+        if ret < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(ret > 0 && pfd.revents & libc::POLLIN != 0)
+    }
+
+    /// Drain up to `max` frames whose `tp_status` has `TP_STATUS_USER` set,
+    /// copying each payload out and resetting the frame to
+    /// `TP_STATUS_KERNEL` so the kernel can reuse it. Frames not yet ready
+    /// are waited for via `poll(2)` rather than treated as "no more data",
+    /// so a batch completes as long as the peer keeps sending; a call only
+    /// returns fewer than `max` frames if `poll` itself times out or fails.
+    pub fn recv_batch(&mut self, max: usize) -> Vec<Vec<u8>> {
+        let frames_per_ring = self.geometry.frames_per_ring();
+        let mut received = Vec::with_capacity(max);
+
+        while received.len() < max {
+            let frame = self.rx_frame(self.rx_cursor);
+            let hdr = frame as *mut TpacketHdr;
+            let status = unsafe { (*hdr).tp_status };
+            if status & TP_STATUS_USER == 0 {
+                match self.poll_readable() {
+                    Ok(true) => continue,
+                    Ok(false) | Err(_) => break,
+                }
+            }
+
+            // `tp_mac` is populated by the kernel with the real offset to
+            // the payload, which reserves extra room after the header for
+            // the frame's sockaddr_ll - it is not simply sizeof(TpacketHdr).
+            let mac_offset = unsafe { (*hdr).tp_mac } as usize;
+            let len = unsafe { (*hdr).tp_len } as usize;
+            let mut payload = vec![0u8; len];
+            unsafe {
+                ptr::copy_nonoverlapping(frame.add(mac_offset), payload.as_mut_ptr(), len);
+                (*hdr).tp_status = TP_STATUS_KERNEL;
+            }
+            received.push(payload);
+
+            self.rx_cursor = (self.rx_cursor + 1) % frames_per_ring;
+        }
+
+        received
+    }
+}
+
+fn bind_to_interface(fd: RawFd, interface_index: i32) -> io::Result<()> {
+    let mut addr: sockaddr_ll = unsafe { std::mem::zeroed() };
+    addr.sll_family = AF_PACKET as u16;
+    addr.sll_protocol = (ETH_P_ALL as u16).to_be();
+    addr.sll_ifindex = interface_index;
+
+    let ret = unsafe {
+        libc::bind(
+            fd,
+            &addr as *const sockaddr_ll as *const libc::sockaddr,
+            std::mem::size_of::<sockaddr_ll>() as libc::socklen_t,
+        )
+    };
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+impl Drop for PacketMmapRing {
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.map, self.geometry.ring_bytes() * 2);
+            libc::close(self.fd);
+        }
+    }
+}
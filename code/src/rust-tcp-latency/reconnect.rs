@@ -0,0 +1,231 @@
+//! A vsock endpoint that repairs itself after the peer drops the
+//! connection, instead of the caller having to notice and redial.
+//!
+//! The stream benchmark connects only through this type: a single bounded
+//! exponential backoff that applies both to the initial connect and to any
+//! reconnect triggered by a mid-benchmark `ECONNRESET`/`EPIPE` or an
+//! end-of-stream from a closed peer. The datagram benchmark in `client.rs`
+//! does not use it; it still connects through `vsock_connect`'s fixed
+//! 5-attempt loop inside a flat 1-second outer retry.
+
+use std::convert::TryInto;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::thread;
+use std::time::Instant;
+
+use rust_tcp_io_perf::config;
+use rust_tcp_io_perf::nix::errno::Errno::{ECONNRESET, EAGAIN, EINTR, EPIPE, EWOULDBLOCK};
+use rust_tcp_io_perf::nix::sys::socket::{connect, recv, send, setsockopt, shutdown, socket};
+use rust_tcp_io_perf::nix::sys::socket::{AddressFamily, MsgFlags, Shutdown, SockAddr, SockFlag, SockType};
+use rust_tcp_io_perf::nix::sys::socket::sockopt::{RcvTimeo, SndTimeo};
+use rust_tcp_io_perf::nix::sys::time::TimeVal;
+use rust_tcp_io_perf::nix::unistd::close;
+
+use crate::transport::{backoff_delay, BenchTransport};
+use crate::LoopError;
+
+struct VsockSocket {
+    socket_fd: RawFd,
+}
+
+impl VsockSocket {
+    fn new(socket_fd: RawFd) -> Self {
+        VsockSocket { socket_fd }
+    }
+}
+
+impl Drop for VsockSocket {
+    fn drop(&mut self) {
+        shutdown(self.socket_fd, Shutdown::Both)
+            .unwrap_or_else(|e| eprintln!("Failed to shut socket down: {:?}", e));
+        close(self.socket_fd).unwrap_or_else(|e| eprintln!("Failed to close socket: {:?}", e));
+    }
+}
+
+impl AsRawFd for VsockSocket {
+    fn as_raw_fd(&self) -> RawFd {
+        self.socket_fd
+    }
+}
+
+fn is_reconnectable(err: &nix::Error) -> bool {
+    matches!(err, nix::Error::Sys(ECONNRESET) | nix::Error::Sys(EPIPE))
+}
+
+fn is_timeout(err: &nix::Error) -> bool {
+    matches!(err, nix::Error::Sys(EAGAIN) | nix::Error::Sys(EWOULDBLOCK))
+}
+
+/// Single connection attempt on an AF_VSOCK socket; no retries of its own,
+/// since `ReconnectSocket` owns the retry/backoff policy. `timeout_ms`, when
+/// set, is applied to the connected socket as `SO_SNDTIMEO`/`SO_RCVTIMEO`.
+fn dial(cid: u32, port: u32, sock_type: SockType, timeout_ms: Option<u64>) -> Result<VsockSocket, String> {
+    let sockaddr = SockAddr::new_vsock(cid, port);
+    let vsocket = VsockSocket::new(
+        socket(AddressFamily::Vsock, sock_type, SockFlag::empty(), None)
+            .map_err(|err| format!("Failed to create the socket: {:?}", err))?,
+    );
+    connect(vsocket.as_raw_fd(), &sockaddr).map_err(|err| format!("Failed to connect: {}", err))?;
+
+    if let Some(timeout_ms) = timeout_ms {
+        let timeout = TimeVal::milliseconds(timeout_ms as i64);
+        setsockopt(vsocket.as_raw_fd(), SndTimeo, &timeout)
+            .map_err(|err| format!("Failed to set send timeout: {:?}", err))?;
+        setsockopt(vsocket.as_raw_fd(), RcvTimeo, &timeout)
+            .map_err(|err| format!("Failed to set receive timeout: {:?}", err))?;
+    }
+
+    Ok(vsocket)
+}
+
+/// A vsock socket that transparently redials with bounded exponential
+/// backoff (`transport::backoff_delay`) whenever a send/recv fails with
+/// `ECONNRESET`/`EPIPE`, and resets the backoff on success.
+pub struct ReconnectSocket {
+    cid: u32,
+    port: u32,
+    sock_type: SockType,
+    timeout_ms: Option<u64>,
+    socket: VsockSocket,
+    next_try: Instant,
+    attempt: u32,
+    reconnects: u64,
+}
+
+impl ReconnectSocket {
+    /// Establish the initial connection, retrying with backoff until it
+    /// succeeds. This is the only place the caller can still block forever,
+    /// same as the original `vsock_connect` + outer `while !connected` loop.
+    pub fn connect(cid: u32, port: u32, sock_type: SockType, timeout_ms: Option<u64>) -> Self {
+        let mut attempt = 0;
+        loop {
+            match dial(cid, port, sock_type, timeout_ms) {
+                Ok(socket) => {
+                    return ReconnectSocket {
+                        cid,
+                        port,
+                        sock_type,
+                        timeout_ms,
+                        socket,
+                        next_try: Instant::now(),
+                        attempt: 0,
+                        reconnects: 0,
+                    }
+                }
+                Err(error) => {
+                    println!("Couldn't connect to server, retrying... Error {}", error);
+                    thread::sleep(backoff_delay(attempt));
+                    attempt += 1;
+                }
+            }
+        }
+    }
+
+    /// Total number of times this socket has redialed after a mid-benchmark
+    /// disconnect, so the caller can report reconnects separately from the
+    /// latency histogram instead of letting them pollute it.
+    pub fn reconnects(&self) -> u64 {
+        self.reconnects
+    }
+
+    fn reconnect(&mut self) {
+        let now = Instant::now();
+        if now < self.next_try {
+            thread::sleep(self.next_try - now);
+        }
+        match dial(self.cid, self.port, self.sock_type, self.timeout_ms) {
+            Ok(socket) => {
+                self.socket = socket;
+                self.attempt = 0;
+                self.next_try = Instant::now();
+                self.reconnects += 1;
+            }
+            Err(_) => {
+                self.next_try = Instant::now() + backoff_delay(self.attempt);
+                self.attempt += 1;
+            }
+        }
+    }
+
+    pub fn as_raw_fd(&self) -> RawFd {
+        self.socket.as_raw_fd()
+    }
+
+    /// Send `len` bytes, transparently reconnecting and restarting the send
+    /// from the beginning if the peer resets the connection mid-transfer.
+    /// A send timeout is returned as `LoopError::Timeout` rather than
+    /// triggering a reconnect, since the peer may simply be slow.
+    pub fn send_loop(&mut self, buf: &[u8], len: u64) -> Result<(), LoopError> {
+        let len: usize = len.try_into().map_err(|err| LoopError::Other(format!("{:?}", err)))?;
+
+        'redial: loop {
+            let mut send_bytes = 0;
+            while send_bytes < len {
+                match send(self.as_raw_fd(), &buf[send_bytes..len], MsgFlags::empty()) {
+                    Ok(size) => send_bytes += size,
+                    Err(nix::Error::Sys(EINTR)) => {}
+                    Err(err) if is_timeout(&err) => return Err(LoopError::Timeout),
+                    Err(err) if is_reconnectable(&err) => {
+                        self.reconnect();
+                        continue 'redial;
+                    }
+                    Err(err) => return Err(LoopError::Other(format!("{:?}", err))),
+                }
+            }
+            return Ok(());
+        }
+    }
+
+    /// Receive `len` bytes, transparently reconnecting and restarting the
+    /// receive from the beginning if the peer resets or closes the connection
+    /// mid-transfer. A receive timeout is returned as `LoopError::Timeout`
+    /// rather than triggering a reconnect, since the peer may simply be slow.
+    pub fn recv_loop(&mut self, buf: &mut [u8], len: u64) -> Result<(), LoopError> {
+        let len: usize = len.try_into().map_err(|err| LoopError::Other(format!("{:?}", err)))?;
+
+        'redial: loop {
+            let mut recv_bytes = 0;
+            while recv_bytes < len {
+                match recv(self.as_raw_fd(), &mut buf[recv_bytes..len], MsgFlags::empty()) {
+                    // End of stream: the peer closed the connection (e.g. a
+                    // server restart), so redial instead of spinning on 0.
+                    Ok(0) => {
+                        self.reconnect();
+                        continue 'redial;
+                    }
+                    Ok(size) => recv_bytes += size,
+                    Err(nix::Error::Sys(EINTR)) => {}
+                    Err(err) if is_timeout(&err) => return Err(LoopError::Timeout),
+                    Err(err) if is_reconnectable(&err) => {
+                        self.reconnect();
+                        continue 'redial;
+                    }
+                    Err(err) => return Err(LoopError::Other(format!("{:?}", err))),
+                }
+            }
+            return Ok(());
+        }
+    }
+}
+
+impl BenchTransport for ReconnectSocket {
+    /// `args.address` is not used here: the Unix transport always talks to
+    /// the local vsock server at cid 16, port 5001.
+    fn connect(args: &config::Args) -> Self {
+        ReconnectSocket::connect(16, 5001, SockType::Stream, args.timeout_ms)
+    }
+
+    fn send_loop(&mut self, buf: &[u8], len: u64) -> Result<(), LoopError> {
+        ReconnectSocket::send_loop(self, buf, len)
+    }
+
+    fn recv_loop(&mut self, buf: &mut [u8], len: u64) -> Result<(), LoopError> {
+        ReconnectSocket::recv_loop(self, buf, len)
+    }
+
+    fn reconnects(&self) -> u64 {
+        ReconnectSocket::reconnects(self)
+    }
+
+    fn close(self) {}
+}
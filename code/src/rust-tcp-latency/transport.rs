@@ -0,0 +1,46 @@
+//! Platform-agnostic benchmark transport.
+//!
+//! `client.rs`'s measurement loops used to be hardwired to `RawFd` and the
+//! `nix` crate, which only exist on Unix. `BenchTransport` factors the
+//! connect/send/recv surface they need into a trait so the same loops can
+//! run against a Unix vsock socket (`reconnect::ReconnectSocket`) or a
+//! Windows TCP socket (`windows::TcpTransport`), selected at compile time.
+
+use std::time::Duration;
+
+use rust_tcp_io_perf::config;
+
+use crate::LoopError;
+
+const MIN_BACKOFF: Duration = Duration::from_millis(50);
+const MAX_BACKOFF: Duration = Duration::from_secs(10);
+
+/// Bounded exponential backoff delay for the `attempt`'th retry (0-indexed),
+/// shared by the Unix vsock transport (`reconnect::ReconnectSocket`) and the
+/// Windows TCP transport (`windows::dial`) so both redial policies actually
+/// match, rather than one growing the delay and the other sleeping a flat
+/// interval.
+pub fn backoff_delay(attempt: u32) -> Duration {
+    MIN_BACKOFF
+        .checked_mul(1 << attempt.min(16))
+        .unwrap_or(MAX_BACKOFF)
+        .min(MAX_BACKOFF)
+}
+
+pub trait BenchTransport: Sized {
+    /// Establish the connection, retrying until it succeeds.
+    fn connect(args: &config::Args) -> Self;
+
+    /// Send `len` bytes, looping until the whole payload is out.
+    fn send_loop(&mut self, buf: &[u8], len: u64) -> Result<(), LoopError>;
+
+    /// Receive `len` bytes, looping until the whole payload is in.
+    fn recv_loop(&mut self, buf: &mut [u8], len: u64) -> Result<(), LoopError>;
+
+    /// Number of times this transport has silently redialed after a
+    /// mid-benchmark disconnect.
+    fn reconnects(&self) -> u64;
+
+    /// Tear the connection down.
+    fn close(self);
+}
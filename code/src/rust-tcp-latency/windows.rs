@@ -0,0 +1,113 @@
+//! TCP-only `BenchTransport` for non-Unix targets.
+//!
+//! There is no `AF_VSOCK` on Windows and the rest of this benchmark is built
+//! on the `nix` crate, which does not compile there either, so this side of
+//! `#[cfg(windows)]` talks plain `std::net::TcpStream` to `args.address`
+//! instead. It supports the same reconnect-with-backoff and send/receive
+//! timeout behaviour as the Unix vsock transport, just via the portable
+//! `std::net` / `std::io` APIs rather than raw Winsock.
+
+use std::io::{Read, Write};
+use std::net::{Shutdown, TcpStream};
+use std::thread;
+use std::time::Duration;
+
+use rust_tcp_io_perf::config;
+
+use crate::transport::{backoff_delay, BenchTransport};
+use crate::LoopError;
+
+fn is_timeout(err: &std::io::Error) -> bool {
+    matches!(err.kind(), std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut)
+}
+
+fn is_reconnectable(err: &std::io::Error) -> bool {
+    matches!(err.kind(), std::io::ErrorKind::ConnectionReset | std::io::ErrorKind::BrokenPipe)
+}
+
+fn dial(address: &str, timeout_ms: Option<u64>) -> TcpStream {
+    let mut attempt = 0;
+    loop {
+        match TcpStream::connect(address) {
+            Ok(stream) => {
+                // A zero SO_RCVTIMEO/SO_SNDTIMEO timeval means "no timeout"
+                // on the Unix vsock path, but `std::net`'s setters reject
+                // `Duration::ZERO` with an `Err` instead of treating it the
+                // same way, so `timeout_ms: Some(0)` is mapped to `None`
+                // here to keep the two platforms' "0 means unbounded"
+                // semantics consistent rather than panicking.
+                let timeout = timeout_ms.filter(|&ms| ms > 0).map(Duration::from_millis);
+                if timeout.is_some() {
+                    stream.set_read_timeout(timeout).expect("Failed to set read timeout");
+                    stream.set_write_timeout(timeout).expect("Failed to set write timeout");
+                }
+                return stream;
+            }
+            Err(error) => {
+                println!("Couldn't connect to server, retrying... Error {}", error);
+                thread::sleep(backoff_delay(attempt));
+                attempt += 1;
+            }
+        }
+    }
+}
+
+pub struct TcpTransport {
+    address: String,
+    timeout_ms: Option<u64>,
+    stream: TcpStream,
+    reconnects: u64,
+}
+
+impl TcpTransport {
+    fn reconnect(&mut self) {
+        self.stream = dial(&self.address, self.timeout_ms);
+        self.reconnects += 1;
+    }
+}
+
+impl BenchTransport for TcpTransport {
+    fn connect(args: &config::Args) -> Self {
+        TcpTransport {
+            address: args.address.clone(),
+            timeout_ms: args.timeout_ms,
+            stream: dial(&args.address, args.timeout_ms),
+            reconnects: 0,
+        }
+    }
+
+    fn send_loop(&mut self, buf: &[u8], len: u64) -> Result<(), LoopError> {
+        let len = len as usize;
+        loop {
+            match self.stream.write_all(&buf[..len]) {
+                Ok(()) => return Ok(()),
+                Err(err) if is_timeout(&err) => return Err(LoopError::Timeout),
+                Err(err) if is_reconnectable(&err) => self.reconnect(),
+                Err(err) => return Err(LoopError::Other(format!("{:?}", err))),
+            }
+        }
+    }
+
+    fn recv_loop(&mut self, buf: &mut [u8], len: u64) -> Result<(), LoopError> {
+        let len = len as usize;
+        loop {
+            match self.stream.read_exact(&mut buf[..len]) {
+                Ok(()) => return Ok(()),
+                Err(err) if is_timeout(&err) => return Err(LoopError::Timeout),
+                // End of stream means the peer closed the connection.
+                Err(err) if is_reconnectable(&err) || err.kind() == std::io::ErrorKind::UnexpectedEof => {
+                    self.reconnect()
+                }
+                Err(err) => return Err(LoopError::Other(format!("{:?}", err))),
+            }
+        }
+    }
+
+    fn reconnects(&self) -> u64 {
+        self.reconnects
+    }
+
+    fn close(self) {
+        let _ = self.stream.shutdown(Shutdown::Both);
+    }
+}